@@ -1,6 +1,9 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{generate, Shell};
+use clap_complete_nushell::Nushell;
 use colored::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::error::Error;
 use std::fs;
 use std::fs::File;
@@ -11,11 +14,16 @@ use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use which::which;
 
-fn new_package(package_name: &str) -> std::io::Result<()> {
+fn new_package(package_name: &str, lib: bool) -> std::io::Result<()> {
     if !Path::new(package_name).exists() {
         println!(
-            "    {} binary (application) `{}` package",
+            "    {} {} `{}` package",
             "Created".green(),
+            if lib {
+                "library"
+            } else {
+                "binary (application)"
+            },
             package_name
         );
         fs::create_dir(package_name)?;
@@ -28,38 +36,141 @@ fn new_package(package_name: &str) -> std::io::Result<()> {
         write!(
             file,
             r#"[package]
-name = "{}"
+name = "{name}"
 version = "0.1.0"
 edition = "2023"
+kind = "{kind}"
 
 [dependencies]"#,
-            package_name
+            name = package_name,
+            kind = if lib { "lib" } else { "bin" }
         )?;
 
-        let mut file = File::create(PathBuf::from(package_name).join("src").join("BUILD"))?;
+        if lib {
+            let mut file = File::create(PathBuf::from(package_name).join("src").join("BUILD"))?;
 
-        write!(
-            file,
-            r#"load("@rules_cc//cc:defs.bzl", "cc_binary")
+            write!(
+                file,
+                r#"load("@rules_cc//cc:defs.bzl", "cc_library", "cc_test")
+
+cc_library(
+    name = "{name}",
+    srcs = ["{name}.cc"],
+    hdrs = ["{name}.h"],
+)
+
+cc_test(
+    name = "{name}_test",
+    srcs = ["main_test.cc"],
+    deps = [":{name}"],
+)"#,
+                name = package_name
+            )?;
+
+            let mut file = File::create(
+                PathBuf::from(package_name)
+                    .join("src")
+                    .join(format!("{}.h", package_name)),
+            )?;
+
+            write!(
+                file,
+                r#"#pragma once
+
+#include <string>
+
+std::string get_greet(const std::string& who);"#
+            )?;
+
+            let mut file = File::create(
+                PathBuf::from(package_name)
+                    .join("src")
+                    .join(format!("{}.cc", package_name)),
+            )?;
+
+            write!(
+                file,
+                r#"#include "{name}.h"
+
+std::string get_greet(const std::string& who) {{
+  return "Hello " + who;
+}}"#,
+                name = package_name
+            )?;
+
+            let mut file =
+                File::create(PathBuf::from(package_name).join("src").join("main_test.cc"))?;
+
+            write!(
+                file,
+                r#"#include <cassert>
+
+#include "{name}.h"
+
+int main() {{
+  assert(get_greet("world") == "Hello world");
+  return 0;
+}}"#,
+                name = package_name
+            )?;
+        } else {
+            let mut file = File::create(PathBuf::from(package_name).join("src").join("BUILD"))?;
+
+            write!(
+                file,
+                r#"load("@rules_cc//cc:defs.bzl", "cc_binary", "cc_library", "cc_test")
+
+cc_library(
+    name = "{name}_lib",
+    srcs = ["greet.cc"],
+    hdrs = ["greet.h"],
+)
 
 cc_binary(
-    name = "{}",
+    name = "{name}",
     srcs = ["main.cc"],
+    deps = [":{name}_lib"],
+)
+
+cc_test(
+    name = "{name}_test",
+    srcs = ["main_test.cc"],
+    deps = [":{name}_lib"],
 )"#,
-            package_name
-        )?;
+                name = package_name
+            )?;
 
-        let mut file = File::create(PathBuf::from(package_name).join("src").join("main.cc"))?;
+            let mut file = File::create(PathBuf::from(package_name).join("src").join("greet.h"))?;
+
+            write!(
+                file,
+                r#"#pragma once
 
-        write!(
-            file,
-            r#"#include <ctime>
 #include <string>
-#include <iostream>
+
+std::string get_greet(const std::string& who);"#
+            )?;
+
+            let mut file = File::create(PathBuf::from(package_name).join("src").join("greet.cc"))?;
+
+            write!(
+                file,
+                r#"#include "greet.h"
 
 std::string get_greet(const std::string& who) {{
   return "Hello " + who;
-}}
+}}"#
+            )?;
+
+            let mut file = File::create(PathBuf::from(package_name).join("src").join("main.cc"))?;
+
+            write!(
+                file,
+                r#"#include <ctime>
+#include <iostream>
+#include <string>
+
+#include "greet.h"
 
 void print_localtime() {{
   std::time_t result = std::time(nullptr);
@@ -75,7 +186,23 @@ int main(int argc, char** argv) {{
   print_localtime();
   return 0;
 }}"#
-        )?;
+            )?;
+
+            let mut file =
+                File::create(PathBuf::from(package_name).join("src").join("main_test.cc"))?;
+
+            write!(
+                file,
+                r#"#include <cassert>
+
+#include "greet.h"
+
+int main() {{
+  assert(get_greet("world") == "Hello world");
+  return 0;
+}}"#
+            )?;
+        }
 
         Ok(())
     } else {
@@ -88,6 +215,299 @@ int main(int argc, char** argv) {{
     }
 }
 
+fn hash_archive(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    format!("{:x}", hasher.finalize())
+}
+
+fn load_lockfile() -> Option<Lockfile> {
+    let content = fs::read_to_string("Buddy.lock").ok()?;
+    toml::from_str(&content).ok()
+}
+
+fn lockfile_matches(lockfile: &Lockfile, config: &Config) -> bool {
+    if lockfile.package.len() != config.dependencies.len() {
+        return false;
+    }
+
+    config.dependencies.values().all(|dependency| {
+        lockfile
+            .package
+            .iter()
+            .any(|locked| locked.name == dependency.name && locked.version == dependency.version)
+    })
+}
+
+fn write_lockfile(config: &Config) -> Result<Lockfile, Box<dyn Error>> {
+    let mut package = Vec::new();
+
+    for dependency in config.dependencies.values() {
+        let source = dependency.url.clone();
+        let bytes = reqwest::blocking::get(&source)?
+            .error_for_status()?
+            .bytes()?;
+        let checksum = hash_archive(&bytes);
+
+        package.push(LockedDependency {
+            name: dependency.name.clone(),
+            version: dependency.version.clone(),
+            source,
+            checksum,
+        });
+    }
+
+    let lockfile = Lockfile { package };
+
+    let mut file = File::create("Buddy.lock")?;
+    file.write_all(toml::to_string_pretty(&lockfile)?.as_bytes())?;
+
+    Ok(lockfile)
+}
+
+fn resolve_lockfile(config: &Config) -> Result<Lockfile, Box<dyn Error>> {
+    if let Some(lockfile) = load_lockfile() {
+        if lockfile_matches(&lockfile, config) {
+            return Ok(lockfile);
+        }
+    }
+
+    write_lockfile(config)
+}
+
+fn default_build_template(config: &Config, deps: &str) -> String {
+    if config.package.kind == "lib" {
+        format!(
+            r#"load("@rules_cc//cc:defs.bzl", "cc_library", "cc_test")
+
+cc_library(
+    name = "{name}",
+    srcs = ["{name}.cc"],
+    hdrs = ["{name}.h"]{deps}
+)
+
+cc_test(
+    name = "{name}_test",
+    srcs = ["main_test.cc"],
+    deps = [":{name}"]
+)"#,
+            name = config.package.name,
+            deps = deps
+        )
+    } else {
+        format!(
+            r#"load("@rules_cc//cc:defs.bzl", "cc_binary", "cc_library", "cc_test")
+
+cc_library(
+    name = "{name}_lib",
+    srcs = ["greet.cc"],
+    hdrs = ["greet.h"]{deps}
+)
+
+cc_binary(
+    name = "{name}",
+    srcs = ["main.cc"],
+    deps = [":{name}_lib"]
+)
+
+cc_test(
+    name = "{name}_test",
+    srcs = ["main_test.cc"],
+    deps = [":{name}_lib"]
+)"#,
+            name = config.package.name,
+            deps = deps
+        )
+    }
+}
+
+/// Returns the index of the `close` character that balances the `open`
+/// character found at `content[open_idx]`.
+fn find_matching_bracket(content: &str, open_idx: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, c) in content[open_idx..].char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(open_idx + i);
+            }
+        }
+    }
+    None
+}
+
+/// Finds the `(...)`  span of the rule whose `name` attribute is `rule_name`,
+/// returning the byte range of the call including both parens.
+fn find_rule_span(content: &str, rule_name: &str) -> Option<(usize, usize)> {
+    let needle = format!(r#"name = "{}""#, rule_name);
+    let name_idx = content.find(&needle)?;
+
+    let mut depth = 0i32;
+    let mut open_idx = None;
+    for (i, c) in content[..name_idx].char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' => {
+                if depth == 0 {
+                    open_idx = Some(i);
+                    break;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+
+    let open_idx = open_idx?;
+    let close_idx = find_matching_bracket(content, open_idx, '(', ')')?;
+    Some((open_idx, close_idx))
+}
+
+/// A `deps` label we generate ourselves always has the shape `@name//:name`;
+/// anything else was added by hand and must be preserved across a splice.
+fn is_auto_dependency_label(label: &str) -> bool {
+    label
+        .strip_prefix('@')
+        .and_then(|rest| rest.split_once("//:"))
+        .is_some_and(|(pkg, target)| pkg == target)
+}
+
+/// Replaces the `deps = [...]` attribute of the named rule within `content`
+/// with `deps_labels`, preserving every other rule and attribute byte for
+/// byte. Hand-added labels already present on the rule (anything that isn't
+/// one of our own `@name//:name` labels) are kept. Returns `None` if the
+/// rule can't be found, so the caller can leave the file untouched instead
+/// of guessing.
+fn splice_build_deps(content: &str, rule_name: &str, deps_labels: &[String]) -> Option<String> {
+    let (rule_start, rule_end) = find_rule_span(content, rule_name)?;
+    let rule = &content[rule_start..=rule_end];
+
+    let new_rule = if let Some(attr_idx) = rule.find("deps = [") {
+        let bracket_open = attr_idx + "deps = [".len() - 1;
+        let bracket_close = find_matching_bracket(rule, bracket_open, '[', ']')?;
+
+        let mut labels: Vec<String> = rule[bracket_open + 1..bracket_close]
+            .split(',')
+            .map(|entry| entry.trim().trim_matches('"').to_string())
+            .filter(|entry| !entry.is_empty())
+            .filter(|entry| !is_auto_dependency_label(entry))
+            .collect();
+        labels.extend(deps_labels.iter().cloned());
+
+        let new_list = labels
+            .iter()
+            .map(|label| format!("\n        \"{}\",", label))
+            .collect::<String>();
+        let new_list = if new_list.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n    ", new_list)
+        };
+
+        format!(
+            "{}{}{}",
+            &rule[..bracket_open + 1],
+            new_list,
+            &rule[bracket_close..]
+        )
+    } else if !deps_labels.is_empty() {
+        let close_paren = rule.len() - 1;
+        let prefix = rule[..close_paren].trim_end();
+        let needs_comma = !prefix.ends_with(',');
+        let list = deps_labels
+            .iter()
+            .map(|label| format!("        \"{}\",\n", label))
+            .collect::<String>();
+
+        format!(
+            "{}{}\n    deps = [\n{}    ],\n){}",
+            &rule[..prefix.len()],
+            if needs_comma { "," } else { "" },
+            list,
+            &rule[close_paren + 1..]
+        )
+    } else {
+        rule.to_string()
+    };
+
+    Some(format!(
+        "{}{}{}",
+        &content[..rule_start],
+        new_rule,
+        &content[rule_end + 1..]
+    ))
+}
+
+fn resolve_dependencies(config: &Config, lockfile: &Lockfile) -> std::io::Result<()> {
+    let mut workspace = File::create("WORKSPACE")?;
+
+    if !lockfile.package.is_empty() {
+        writeln!(
+            workspace,
+            "load(\"@bazel_tools//tools/build_defs/repo:http.bzl\", \"http_archive\")\n"
+        )?;
+
+        for locked in &lockfile.package {
+            writeln!(
+                workspace,
+                r#"http_archive(
+    name = "{name}",
+    urls = ["{source}"],
+    sha256 = "{checksum}",
+)
+"#,
+                name = locked.name,
+                source = locked.source,
+                checksum = locked.checksum
+            )?;
+        }
+    }
+
+    let deps_labels: Vec<String> = config
+        .dependencies
+        .values()
+        .map(|dependency| format!("@{}//:{}", dependency.name, dependency.name))
+        .collect();
+
+    let build_path = Path::new("src").join("BUILD");
+    let rule_name = if config.package.kind == "lib" {
+        config.package.name.clone()
+    } else {
+        format!("{}_lib", config.package.name)
+    };
+
+    match fs::read_to_string(&build_path) {
+        Ok(content) => {
+            // Splice just the `deps` attribute in place so hand-edited
+            // rules and files elsewhere in `src/BUILD` survive untouched.
+            // If the expected rule can't be found, leave the file alone
+            // rather than guessing and clobbering it.
+            if let Some(updated) = splice_build_deps(&content, &rule_name, &deps_labels) {
+                let mut build_file = File::create(&build_path)?;
+                build_file.write_all(updated.as_bytes())?;
+            }
+        }
+        Err(_) => {
+            let deps = if deps_labels.is_empty() {
+                String::new()
+            } else {
+                let joined = deps_labels
+                    .iter()
+                    .map(|label| format!(r#"        "{}""#, label))
+                    .collect::<Vec<_>>()
+                    .join(",\n");
+                format!(",\n    deps = [\n{},\n    ]", joined)
+            };
+            let mut build_file = File::create(&build_path)?;
+            build_file.write_all(default_build_template(config, &deps).as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
 fn build(bazel_bin: &PathBuf, args: &[String]) -> Result<(), Box<dyn Error>> {
     let mut cmd = Command::new(bazel_bin);
 
@@ -95,7 +515,7 @@ fn build(bazel_bin: &PathBuf, args: &[String]) -> Result<(), Box<dyn Error>> {
     cmd.arg("build");
     cmd.arg("--symlink_prefix=target/");
 
-    if args.len() != 0 {
+    if !args.is_empty() {
         for arg in args {
             cmd.arg(arg);
         }
@@ -121,11 +541,7 @@ fn build(bazel_bin: &PathBuf, args: &[String]) -> Result<(), Box<dyn Error>> {
         }
     }
 
-    // Not sure why is still being generated. Eitherway, we get rid of it.
-    let folder_path = Path::new("bazel-out");
-    if folder_path.exists() {
-        fs::remove_dir_all(folder_path).expect("Failed to delete folder");
-    }
+    child.wait()?;
 
     Ok(())
 }
@@ -137,7 +553,7 @@ fn run(bazel_bin: &PathBuf, args: &[String], config: &Config) -> Result<(), Box<
     cmd.arg("run");
     cmd.arg("--symlink_prefix=target/");
 
-    if args.len() != 0 {
+    if !args.is_empty() {
         for arg in args {
             cmd.arg(arg);
         }
@@ -163,12 +579,156 @@ fn run(bazel_bin: &PathBuf, args: &[String], config: &Config) -> Result<(), Box<
         }
     }
 
-    // Not sure why is still being generated. Eitherway, we get rid of it.
-    let folder_path = Path::new("bazel-out");
-    if folder_path.exists() {
-        fs::remove_dir_all(folder_path).expect("Failed to delete folder");
+    child.wait()?;
+
+    Ok(())
+}
+
+fn test(bazel_bin: &PathBuf, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::new(bazel_bin);
+
+    cmd.arg("--output_base=target/build");
+    cmd.arg("test");
+    cmd.arg("--symlink_prefix=target/");
+
+    if !args.is_empty() {
+        for arg in args {
+            cmd.arg(arg);
+        }
+    } else {
+        cmd.arg("//src/...");
     }
 
+    let mut child = cmd
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+
+    let stderr = child.stderr.take().unwrap();
+    let reader = io::BufReader::new(stderr);
+
+    for line in reader.lines() {
+        let line = line.unwrap();
+        if line.starts_with("INFO:") {
+            let (_, message) = line.split_at(6);
+            println!("{} {}", "INFO:".green(), message);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    child.wait()?;
+
+    Ok(())
+}
+
+fn clean(bazel_bin: &PathBuf, expunge: bool) -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::new(bazel_bin);
+
+    cmd.arg("--output_base=target/build");
+    cmd.arg("clean");
+
+    if expunge {
+        cmd.arg("--expunge");
+    }
+
+    let mut child = cmd
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+
+    let stderr = child.stderr.take().unwrap();
+    let reader = io::BufReader::new(stderr);
+
+    for line in reader.lines() {
+        let line = line.unwrap();
+        if line.starts_with("INFO:") {
+            let (_, message) = line.split_at(6);
+            println!("{} {}", "INFO:".green(), message);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    child.wait()?;
+
+    Ok(())
+}
+
+const DEFAULT_DIST_INCLUDE: [&str; 2] = ["README.md", "LICENSE"];
+
+fn dist(bazel_bin: &PathBuf, config: &Config, include: &[String]) -> Result<(), Box<dyn Error>> {
+    if config.package.kind == "lib" {
+        eprintln!(
+            "{}: `dist` only packages binary packages, but `{}` has `kind = \"lib\"`",
+            "error".red(),
+            config.package.name
+        );
+        std::process::exit(1);
+    }
+
+    let mut cmd = Command::new(bazel_bin);
+
+    cmd.arg("--output_base=target/build");
+    cmd.arg("build");
+    cmd.arg("--symlink_prefix=target/");
+    cmd.arg("-c");
+    cmd.arg("opt");
+    cmd.arg(format!("//src:{}", config.package.name));
+
+    let mut child = cmd
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+
+    let stderr = child.stderr.take().unwrap();
+    let reader = io::BufReader::new(stderr);
+
+    for line in reader.lines() {
+        let line = line.unwrap();
+        if line.starts_with("INFO:") {
+            let (_, message) = line.split_at(6);
+            println!("{} {}", "INFO:".green(), message);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    child.wait()?;
+
+    let archive_name = format!("{}-{}.tar.gz", config.package.name, config.package.version);
+    let archive_path = Path::new("target").join(&archive_name);
+    let archive_file = File::create(&archive_path)?;
+    let encoder = flate2::write::GzEncoder::new(archive_file, flate2::Compression::default());
+    let mut tar = tar::Builder::new(encoder);
+
+    let binary_path = Path::new("target")
+        .join("bin")
+        .join("src")
+        .join(&config.package.name);
+    tar.append_path_with_name(&binary_path, &config.package.name)?;
+
+    let include: Vec<String> = if include.is_empty() {
+        DEFAULT_DIST_INCLUDE.iter().map(|f| f.to_string()).collect()
+    } else {
+        include.to_vec()
+    };
+
+    for file in &include {
+        let path = Path::new(file);
+        if path.exists() {
+            tar.append_path_with_name(path, file)?;
+        }
+    }
+
+    tar.into_inner()?.finish()?;
+
+    println!(
+        "    {} release archive `{}`",
+        "Created".green(),
+        archive_path.display()
+    );
+
     Ok(())
 }
 
@@ -183,13 +743,50 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new buddy package
-    New { path: String },
+    New {
+        path: String,
+
+        /// Create a library package instead of a binary
+        #[arg(long)]
+        lib: bool,
+    },
 
     /// Compile the current package
     Build { targets: Vec<String> },
 
     /// Run a binary or example of the local package
     Run { targets: Vec<String> },
+
+    /// Run the tests
+    Test { targets: Vec<String> },
+
+    /// Remove build outputs with `bazel clean`
+    Clean {
+        /// Also remove the whole Bazel output base (`target/build`), not just the build outputs
+        #[arg(long)]
+        expunge: bool,
+    },
+
+    /// Build a release tarball
+    Dist { include: Vec<String> },
+
+    /// Update dependencies and refresh Buddy.lock
+    Update,
+
+    /// Generate shell completions for the given shell
+    Completions { shell: CompletionShell },
+
+    /// Generate a man page for buddy
+    Man,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+    Nushell,
 }
 
 #[derive(Debug, Deserialize)]
@@ -197,12 +794,20 @@ struct Package {
     name: String,
     version: String,
     edition: String,
+    #[serde(default = "default_kind")]
+    kind: String,
+}
+
+fn default_kind() -> String {
+    "bin".to_string()
 }
 
 #[derive(Debug, Deserialize)]
 struct Dependency {
     name: String,
     version: String,
+    /// URL of a Bazel-consumable archive (e.g. a tagged source tarball with a `BUILD` file)
+    url: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -211,9 +816,34 @@ struct Config {
     dependencies: std::collections::BTreeMap<String, Dependency>,
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Debug, Serialize, Deserialize)]
+struct LockedDependency {
+    name: String,
+    version: String,
+    source: String,
+    checksum: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Lockfile {
+    package: Vec<LockedDependency>,
+}
+
+const VALID_EDITIONS: [&str; 5] = ["2011", "2014", "2017", "2020", "2023"];
+
+fn validate_edition(edition: &str) -> Result<(), String> {
+    if VALID_EDITIONS.contains(&edition) {
+        Ok(())
+    } else {
+        Err(format!(
+            "unsupported edition `{}`, expected one of {}",
+            edition,
+            VALID_EDITIONS.join(", ")
+        ))
+    }
+}
 
+fn load_environment() -> (PathBuf, Config) {
     let bazel_bin = match which("bazel") {
         Ok(path) => path,
         Err(_) => panic!("Bazel binary not found. See https://bazel.build/install"),
@@ -221,12 +851,25 @@ fn main() {
 
     let file_path = "Buddy.toml";
     let config: Config = match fs::read_to_string(file_path) {
-        Ok(content) => toml::from_str(&content).unwrap(),
+        Ok(content) => match toml::from_str::<Config>(&content) {
+            Ok(config) => {
+                if let Err(err) = validate_edition(&config.package.edition) {
+                    eprintln!("{}: {}", "error".red(), err);
+                    std::process::exit(1);
+                }
+                config
+            }
+            Err(err) => {
+                eprintln!("{}: failed to parse `Buddy.toml`: {}", "error".red(), err);
+                std::process::exit(1);
+            }
+        },
         Err(_) => Config {
             package: Package {
                 name: "default".to_string(),
                 version: "0.1.0".to_string(),
-                edition: "2021".to_string(),
+                edition: "2023".to_string(),
+                kind: default_kind(),
             },
             dependencies: std::collections::BTreeMap::new(),
         },
@@ -234,9 +877,64 @@ fn main() {
 
     println!("{:#?}", config);
 
+    (bazel_bin, config)
+}
+
+fn main() {
+    let cli = Cli::parse();
+
     match &cli.command {
-        Commands::New { path } => new_package(&path).unwrap(),
-        Commands::Build { targets } => build(&bazel_bin, &targets).unwrap(),
-        Commands::Run { targets } => run(&bazel_bin, &targets, &config).unwrap(),
+        Commands::New { path, lib } => new_package(path, *lib).unwrap(),
+        Commands::Build { targets } => {
+            let (bazel_bin, config) = load_environment();
+            let lockfile = resolve_lockfile(&config).unwrap();
+            resolve_dependencies(&config, &lockfile).unwrap();
+            build(&bazel_bin, targets).unwrap()
+        }
+        Commands::Run { targets } => {
+            let (bazel_bin, config) = load_environment();
+            let lockfile = resolve_lockfile(&config).unwrap();
+            resolve_dependencies(&config, &lockfile).unwrap();
+            run(&bazel_bin, targets, &config).unwrap()
+        }
+        Commands::Test { targets } => {
+            let (bazel_bin, config) = load_environment();
+            let lockfile = resolve_lockfile(&config).unwrap();
+            resolve_dependencies(&config, &lockfile).unwrap();
+            test(&bazel_bin, targets).unwrap()
+        }
+        Commands::Clean { expunge } => {
+            let (bazel_bin, _config) = load_environment();
+            clean(&bazel_bin, *expunge).unwrap()
+        }
+        Commands::Dist { include } => {
+            let (bazel_bin, config) = load_environment();
+            let lockfile = resolve_lockfile(&config).unwrap();
+            resolve_dependencies(&config, &lockfile).unwrap();
+            dist(&bazel_bin, &config, include).unwrap()
+        }
+        Commands::Update => {
+            let (_bazel_bin, config) = load_environment();
+            write_lockfile(&config).unwrap();
+        }
+        Commands::Completions { shell } => {
+            let mut cmd = Cli::command();
+            let name = cmd.get_name().to_string();
+            match shell {
+                CompletionShell::Bash => generate(Shell::Bash, &mut cmd, name, &mut io::stdout()),
+                CompletionShell::Zsh => generate(Shell::Zsh, &mut cmd, name, &mut io::stdout()),
+                CompletionShell::Fish => generate(Shell::Fish, &mut cmd, name, &mut io::stdout()),
+                CompletionShell::PowerShell => {
+                    generate(Shell::PowerShell, &mut cmd, name, &mut io::stdout())
+                }
+                CompletionShell::Nushell => generate(Nushell, &mut cmd, name, &mut io::stdout()),
+            }
+        }
+        Commands::Man => {
+            let cmd = Cli::command();
+            clap_mangen::Man::new(cmd)
+                .render(&mut io::stdout())
+                .unwrap();
+        }
     }
 }